@@ -4,6 +4,7 @@ use anyhow::{bail, Result};
 use reqwest::RequestBuilder;
 use serde::Deserialize;
 use serde_json::{json, Value};
+use std::collections::HashMap;
 
 const API_BASE: &str = "https://api.openai.com/v1";
 
@@ -15,10 +16,103 @@ pub struct OpenAIResponsesConfig {
     pub organization_id: Option<String>,
     #[serde(default)]
     pub models: Vec<ModelData>,
+    #[serde(default)]
+    pub builtin_tools: Vec<BuiltinTool>,
+    pub reasoning_effort: Option<String>,
+    /// Keep the conversation server-side and chain turns with `previous_response_id`
+    /// instead of resending full history. Defaults to enabled.
+    pub store: Option<bool>,
     pub patch: Option<RequestPatch>,
     pub extra: Option<ExtraConfig>,
 }
 
+/// Tracks the most recent response id per owning `Model` instance, so later turns can chain off
+/// it with `previous_response_id` instead of replaying full history. Response handlers only ever
+/// see `&Model`, not the session/client that owns it, so the key is the `Model`'s own address
+/// combined with its id: each session/client constructs and owns its own `Model` value, so two
+/// sessions configured with the same model name still get distinct, non-colliding addresses.
+/// Capped so a long-running process (e.g. serve mode) can't grow this without bound.
+const MAX_TRACKED_CONVERSATIONS: usize = 1024;
+
+static CONVERSATION_RESPONSE_IDS: std::sync::Mutex<Vec<(String, String)>> =
+    std::sync::Mutex::new(Vec::new());
+
+fn conversation_key(model: &Model) -> String {
+    format!("{:p}:{}", model as *const Model, model.id())
+}
+
+/// Resolves the `previous_response_id` to send for this turn, in one critical section so the
+/// decision to forget-and-return-None and the lookup can't interleave with a concurrent
+/// `remember_response_id` for the same key.
+fn resolve_previous_response_id(key: &str, store: bool, has_prior_assistant_turn: bool) -> Option<String> {
+    let mut state = CONVERSATION_RESPONSE_IDS.lock().unwrap();
+    if !store || !has_prior_assistant_turn {
+        // Either statefulness is off, or this is the first turn of a fresh conversation —
+        // never chain off whatever happens to be cached under this key.
+        state.retain(|(k, _)| k != key);
+        return None;
+    }
+    state.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone())
+}
+
+fn remember_response_id(key: &str, id: Option<String>) {
+    let Some(id) = id else { return };
+    let mut state = CONVERSATION_RESPONSE_IDS.lock().unwrap();
+    match state.iter_mut().find(|(k, _)| k == key) {
+        Some(entry) => entry.1 = id,
+        None => {
+            if state.len() >= MAX_TRACKED_CONVERSATIONS {
+                state.remove(0);
+            }
+            state.push((key.to_string(), id));
+        }
+    }
+}
+
+/// Server-hosted tools the Responses API can invoke itself, without a round trip to this client.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BuiltinTool {
+    WebSearch,
+    FileSearch { vector_store_ids: Vec<String> },
+    CodeInterpreter,
+}
+
+impl BuiltinTool {
+    fn to_json(&self) -> Value {
+        match self {
+            BuiltinTool::WebSearch => json!({ "type": "web_search" }),
+            BuiltinTool::FileSearch { vector_store_ids } => json!({
+                "type": "file_search",
+                "vector_store_ids": vector_store_ids,
+            }),
+            BuiltinTool::CodeInterpreter => json!({
+                "type": "code_interpreter",
+                "container": { "type": "auto" },
+            }),
+        }
+    }
+}
+
+/// Maps a built-in tool's SSE status event to a short human-readable status line, so progress
+/// shows up as prose in the transcript instead of raw internal event names.
+fn builtin_tool_status(event_type: &str) -> Option<&'static str> {
+    match event_type {
+        "response.web_search_call.in_progress" | "response.web_search_call.searching" => {
+            Some("Searching the web…")
+        }
+        "response.web_search_call.completed" => Some("Finished web search."),
+        "response.file_search_call.in_progress" | "response.file_search_call.searching" => {
+            Some("Searching files…")
+        }
+        "response.file_search_call.completed" => Some("Finished file search."),
+        "response.code_interpreter_call.in_progress"
+        | "response.code_interpreter_call.interpreting" => Some("Running code…"),
+        "response.code_interpreter_call.completed" => Some("Finished running code."),
+        _ => None,
+    }
+}
+
 impl OpenAIResponsesClient {
     config_get_fn!(api_key, get_api_key);
     config_get_fn!(api_base, get_api_base);
@@ -48,7 +142,19 @@ fn prepare_chat_completions(
 
     let url = format!("{}/responses", api_base.trim_end_matches('/'));
 
-    let body = openai_build_responses_body(data, &self_.model);
+    let reasoning_effort = self_
+        .config
+        .reasoning_effort
+        .clone()
+        .or_else(|| self_.model.reasoning_effort());
+
+    let body = openai_build_responses_body(
+        data,
+        &self_.model,
+        &self_.config.builtin_tools,
+        reasoning_effort.as_deref(),
+        self_.config.store.unwrap_or(true),
+    );
 
     let mut request_data = RequestData::new(url, body);
 
@@ -64,7 +170,7 @@ fn prepare_chat_completions(
 
 pub async fn openai_responses(
     builder: RequestBuilder,
-    _model: &Model,
+    model: &Model,
 ) -> Result<ChatCompletionsOutput> {
     let res = builder.send().await?;
     let status = res.status();
@@ -74,29 +180,76 @@ pub async fn openai_responses(
     }
 
     debug!("non-stream-data: {data}");
-    openai_extract_responses(&data)
+    let output = openai_extract_responses(&data)?;
+    if data["store"].as_bool() == Some(true) {
+        remember_response_id(&conversation_key(model), output.id.clone());
+    }
+    Ok(output)
+}
+
+/// Accumulates parallel, interleaved function-call streams keyed by `item_id`, since the
+/// Responses API can emit several `function_call` items in one response and their argument
+/// deltas arrive interleaved rather than back-to-back.
+#[derive(Default)]
+struct ToolCallAccumulator {
+    // item_id -> (call_id, name, accumulated arguments)
+    pending: HashMap<String, (String, String, String)>,
+}
+
+impl ToolCallAccumulator {
+    /// Feeds one SSE event. Returns a finalized `ToolCall` when the event completes one.
+    fn handle_event(&mut self, data: &Value) -> Option<ToolCall> {
+        match data["type"].as_str() {
+            Some("response.output_item.added") => {
+                let item = &data["item"];
+                if item["type"].as_str() == Some("function_call") {
+                    if let Some(item_id) = item["id"].as_str() {
+                        let call_id = item["call_id"].as_str().unwrap_or_default().to_string();
+                        let name = item["name"].as_str().unwrap_or_default().to_string();
+                        self.pending.insert(item_id.to_string(), (call_id, name, String::new()));
+                    }
+                }
+                None
+            }
+            Some("response.function_call_arguments.delta") => {
+                if let Some(item_id) = data["item_id"].as_str() {
+                    if let Some(entry) = self.pending.get_mut(item_id) {
+                        if let Some(delta) = data["delta"].as_str() {
+                            entry.2.push_str(delta);
+                        }
+                    }
+                }
+                None
+            }
+            Some("response.function_call_arguments.done") => {
+                data["item_id"].as_str().and_then(|item_id| self.finalize(item_id))
+            }
+            _ => None,
+        }
+    }
+
+    fn finalize(&mut self, item_id: &str) -> Option<ToolCall> {
+        let (call_id, name, arguments) = self.pending.remove(item_id)?;
+        let arguments: Value = serde_json::from_str(&arguments).unwrap_or_default();
+        Some(ToolCall::new(name, arguments, Some(call_id)))
+    }
+
+    /// Finalizes anything left pending once the stream ends (e.g. no explicit `.done` event).
+    fn finalize_remaining(&mut self) -> Vec<ToolCall> {
+        let item_ids: Vec<String> = self.pending.keys().cloned().collect();
+        item_ids.into_iter().filter_map(|item_id| self.finalize(&item_id)).collect()
+    }
 }
 
 pub async fn openai_responses_streaming(
     builder: RequestBuilder,
     handler: &mut SseHandler,
-    _model: &Model,
+    model: &Model,
 ) -> Result<()> {
-    let mut tool_calls: Vec<ToolCall> = Vec::new();
-    let mut current_tool_call_id = String::new();
-    let mut current_tool_function_name = String::new();
-    let mut current_tool_function_arguments = String::new();
+    let mut tool_calls = ToolCallAccumulator::default();
 
     let handle = |message: SseMmessage| -> Result<bool> {
         if message.data == "[DONE]" {
-            if !current_tool_function_name.is_empty() {
-                let arguments: Value = serde_json::from_str(&current_tool_function_arguments).unwrap_or_default();
-                tool_calls.push(ToolCall::new(
-                    current_tool_function_name.clone(),
-                    arguments,
-                    Some(current_tool_call_id.clone()),
-                ));
-            }
             return Ok(true);
         }
 
@@ -109,61 +262,83 @@ pub async fn openai_responses_streaming(
                     handler.text(text)?;
                 }
             }
-            Some("response.tool_code.delta") => {
-                if let Some(id) = data["item_id"].as_str() {
-                    if current_tool_call_id != id {
-                        if !current_tool_function_name.is_empty() {
-                            let arguments: Value = serde_json::from_str(&current_tool_function_arguments).unwrap_or_default();
-                            tool_calls.push(ToolCall::new(
-                                current_tool_function_name.clone(),
-                                arguments,
-                                Some(current_tool_call_id.clone()),
-                            ));
-                        }
-                        current_tool_call_id = id.to_string();
-                        current_tool_function_name.clear();
-                        current_tool_function_arguments.clear();
-                    }
+            Some("response.output_item.added") | Some("response.function_call_arguments.delta") => {
+                tool_calls.handle_event(&data);
+            }
+            Some("response.reasoning_summary_text.delta") => {
+                if let Some(delta) = data["delta"].as_str() {
+                    handler.reasoning(delta)?;
                 }
-                if let Some(part) = data["part"].as_object() {
-                    if let Some(name) = part["function_name"].as_str() {
-                        current_tool_function_name.push_str(name);
-                    }
-                    if let Some(args_chunk) = part["args_chunk"].as_str() {
-                        current_tool_function_arguments.push_str(args_chunk);
+            }
+            Some("response.function_call_arguments.done") => {
+                if let Some(tool_call) = tool_calls.handle_event(&data) {
+                    handler.tool_call(tool_call)?;
+                }
+            }
+            Some("response.completed") => {
+                if data["response"]["store"].as_bool() == Some(true) {
+                    if let Some(id) = data["response"]["id"].as_str() {
+                        remember_response_id(&conversation_key(model), Some(id.to_string()));
                     }
                 }
             }
-            _ => {}
+            Some(type_str) => {
+                if let Some(status) = builtin_tool_status(type_str) {
+                    handler.text(&format!("{status}\n"))?;
+                }
+            }
+            None => {}
         }
         Ok(false)
     };
 
     sse_stream(builder, handle).await?;
 
-    if !tool_calls.is_empty() {
-        for tool_call in tool_calls {
-            handler.tool_call(tool_call)?;
-        }
+    for tool_call in tool_calls.finalize_remaining() {
+        handler.tool_call(tool_call)?;
     }
 
     Ok(())
 }
 
-pub fn openai_build_responses_body(data: ChatCompletionsData, model: &Model) -> Value {
+pub fn openai_build_responses_body(
+    data: ChatCompletionsData,
+    model: &Model,
+    builtin_tools: &[BuiltinTool],
+    default_reasoning_effort: Option<&str>,
+    store: bool,
+) -> Value {
     let ChatCompletionsData {
         messages,
         temperature,
         top_p,
-        functions: _,
+        functions,
         stream,
+        schema,
+        tool_choice,
+        reasoning_effort,
     } = data;
 
-    let (_, history_messages) = messages.split_last().unzip();
-
-    let (instructions, previous_response_id) = extract_history(history_messages.unwrap_or_default());
-
-    let input = build_request_input(&messages);
+    // A per-request override (e.g. set for a single call) wins over the client's
+    // config/model-level default.
+    let reasoning_effort = reasoning_effort.as_deref().or(default_reasoning_effort);
+
+    let key = conversation_key(model);
+    // A prior assistant turn (not just a system prompt) is what marks this as a continuation;
+    // a system+user pair alone is still the first turn of a brand-new conversation.
+    let has_prior_assistant_turn = messages.iter().any(|message| message.role.is_assistant());
+    let previous_response_id = resolve_previous_response_id(&key, store, has_prior_assistant_turn);
+
+    // With a previous_response_id the thread already lives server-side, so only the
+    // new turn needs to go out; otherwise fall back to replaying full history.
+    let (instructions, input) = if previous_response_id.is_some() {
+        let last_message = messages.last().cloned().into_iter().collect::<Vec<_>>();
+        (None, build_request_input(&last_message))
+    } else {
+        let (_, history_messages) = messages.split_last().unzip();
+        let instructions = extract_instructions(history_messages.unwrap_or_default());
+        (instructions, build_request_input(&messages))
+    };
 
     let mut body = json!({
         "model": &model.real_name(),
@@ -176,6 +351,17 @@ pub fn openai_build_responses_body(data: ChatCompletionsData, model: &Model) ->
     if let Some(id) = previous_response_id {
         body["previous_response_id"] = id.into();
     }
+    if store {
+        body["store"] = true.into();
+    }
+    let mut tools = build_responses_tools(&functions).unwrap_or_default();
+    tools.extend(builtin_tools.iter().map(BuiltinTool::to_json));
+    if !tools.is_empty() {
+        body["tools"] = tools.into();
+    }
+    if let Some(tool_choice) = tool_choice {
+        body["tool_choice"] = tool_choice;
+    }
     if let Some(v) = temperature {
         body["temperature"] = v.into();
     }
@@ -185,27 +371,51 @@ pub fn openai_build_responses_body(data: ChatCompletionsData, model: &Model) ->
     if stream {
         body["stream"] = true.into();
     }
+    if let Some(effort) = reasoning_effort {
+        body["reasoning"] = json!({ "effort": effort, "summary": "auto" });
+    }
+    if let Some(max_output_tokens) = model.max_output_tokens() {
+        body["max_output_tokens"] = max_output_tokens.into();
+    }
+    if let Some(schema) = schema {
+        body["text"] = json!({
+            "format": {
+                "type": "json_schema",
+                "name": "response",
+                "schema": schema,
+                "strict": true,
+            }
+        });
+    }
 
     body
 }
 
-fn extract_history(messages: &[Message]) -> (Option<String>, Option<String>) {
-    let mut instructions = None;
-    let mut previous_response_id = None;
-
-    for message in messages {
-        if message.role.is_system() {
-            instructions = Some(message.content.to_text());
-        } else if message.role.is_assistant() {
-            if let MessageContent::Text(text) = &message.content {
-                if let Some(id) = text.strip_prefix("id:").and_then(|s| s.split('\n').next()) {
-                    previous_response_id = Some(id.trim().to_string());
-                }
-            }
-        }
+fn build_responses_tools(functions: &Option<Functions>) -> Option<Vec<Value>> {
+    let functions = functions.as_ref()?;
+    if functions.is_empty() {
+        return None;
     }
+    Some(
+        functions
+            .iter()
+            .map(|function| {
+                json!({
+                    "type": "function",
+                    "name": function.name,
+                    "description": function.description,
+                    "parameters": function.parameters,
+                })
+            })
+            .collect(),
+    )
+}
 
-    (instructions, previous_response_id)
+fn extract_instructions(messages: &[Message]) -> Option<String> {
+    messages
+        .iter()
+        .find(|message| message.role.is_system())
+        .map(|message| message.content.to_text())
 }
 
 fn build_request_input(messages: &Vec<Message>) -> Value {
@@ -242,32 +452,74 @@ fn build_request_input(messages: &Vec<Message>) -> Value {
             json!({"role": role, "content": content})
         }
         (_, MessageContent::ToolCalls(tool_calls)) => {
-            let tool_outputs: Vec<Value> = tool_calls
+            json!(tool_calls
                 .tool_results
                 .iter()
-                .map(|result| {
-                    json!({
-                        "tool_call_id": result.call.id,
-                        "output": result.output
-                    })
-                })
-                .collect();
-            json!([{"role": "user", "content": [{"type": "tool_outputs", "tool_outputs": tool_outputs}]}])
+                .map(|result| json!({
+                    "type": "function_call_output",
+                    "call_id": result.call.id,
+                    "output": result.output
+                }))
+                .collect::<Vec<_>>())
         }
     }).collect::<Value>())
 }
 
 
 pub fn openai_extract_responses(data: &Value) -> Result<ChatCompletionsOutput> {
-    let text = data["output"][0]["content"][0]["text"].as_str().unwrap_or_default().to_string();
+    let mut text = String::new();
+    let mut reasoning = String::new();
+    let mut tool_calls = vec![];
+
+    if let Some(items) = data["output"].as_array() {
+        for item in items {
+            match item["type"].as_str() {
+                Some("message") => {
+                    if let Some(parts) = item["content"].as_array() {
+                        for part in parts {
+                            if let Some(part_text) = part["text"].as_str() {
+                                text.push_str(part_text);
+                            }
+                        }
+                    }
+                }
+                Some("reasoning") => {
+                    if let Some(summary) = item["summary"].as_array() {
+                        for part in summary {
+                            if let Some(part_text) = part["text"].as_str() {
+                                reasoning.push_str(part_text);
+                            }
+                        }
+                    }
+                }
+                Some("function_call") => {
+                    let name = item["name"].as_str().unwrap_or_default().to_string();
+                    let arguments: Value = item["arguments"]
+                        .as_str()
+                        .and_then(|s| serde_json::from_str(s).ok())
+                        .unwrap_or_default();
+                    let call_id = item["call_id"].as_str().map(|v| v.to_string());
+                    tool_calls.push(ToolCall::new(name, arguments, call_id));
+                }
+                Some("web_search_call") | Some("file_search_call") | Some("code_interpreter_call") => {}
+                _ => {}
+            }
+        }
+    }
 
-    if text.is_empty() {
+    if text.is_empty() && tool_calls.is_empty() {
         bail!("Invalid response data: {data}");
     }
 
+    let expects_json_schema = data["text"]["format"]["type"].as_str() == Some("json_schema");
+    if expects_json_schema && !text.is_empty() && serde_json::from_str::<Value>(&text).is_err() {
+        bail!("Invalid response data: expected JSON-schema output but got non-JSON text: {text}");
+    }
+
     let output = ChatCompletionsOutput {
         text,
-        tool_calls: vec![],
+        reasoning: (!reasoning.is_empty()).then_some(reasoning),
+        tool_calls,
         id: data.get("id").and_then(|v| v.as_str()).map(|v| v.to_string()),
         input_tokens: data
             .get("usage")
@@ -281,5 +533,46 @@ pub fn openai_extract_responses(data: &Value) -> Result<ChatCompletionsOutput> {
     Ok(output)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulates_interleaved_parallel_tool_calls_independently() {
+        let mut tool_calls = ToolCallAccumulator::default();
+
+        let added = |item_id: &str, call_id: &str, name: &str| {
+            json!({
+                "type": "response.output_item.added",
+                "item": {"type": "function_call", "id": item_id, "call_id": call_id, "name": name},
+            })
+        };
+        let delta = |item_id: &str, chunk: &str| {
+            json!({"type": "response.function_call_arguments.delta", "item_id": item_id, "delta": chunk})
+        };
+        let done = |item_id: &str| json!({"type": "response.function_call_arguments.done", "item_id": item_id});
+
+        assert!(tool_calls.handle_event(&added("item_a", "call_a", "get_weather")).is_none());
+        assert!(tool_calls.handle_event(&added("item_b", "call_b", "get_time")).is_none());
+
+        // Argument fragments for the two calls arrive interleaved, not back-to-back.
+        assert!(tool_calls.handle_event(&delta("item_a", "{\"city\":")).is_none());
+        assert!(tool_calls.handle_event(&delta("item_b", "{\"zone\":")).is_none());
+        assert!(tool_calls.handle_event(&delta("item_a", "\"nyc\"}")).is_none());
+        assert!(tool_calls.handle_event(&delta("item_b", "\"utc\"}")).is_none());
+
+        let call_a = tool_calls.handle_event(&done("item_a")).expect("item_a should finalize");
+        assert_eq!(call_a.name, "get_weather");
+        assert_eq!(call_a.arguments, json!({"city": "nyc"}));
+        assert_eq!(call_a.id.as_deref(), Some("call_a"));
+
+        // item_b never gets an explicit `.done` event — the stream just ends.
+        let remaining = tool_calls.finalize_remaining();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].name, "get_time");
+        assert_eq!(remaining[0].arguments, json!({"zone": "utc"}));
+        assert_eq!(remaining[0].id.as_deref(), Some("call_b"));
+    }
+}
 
 